@@ -1,28 +1,78 @@
+use std::fmt;
+
+/// 错误类别，供`Error::custom`选择构造的变体：`BadRequest`与`NotFound`归入`Error::BadRequest`，
+/// `InternalServerError`归入`Error::Internal`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    BadRequest,
+    NotFound,
+    InternalServerError,
+}
+
+/// 错误类型，以变体保留底层错误及其`source()`链，
+/// 使调用方可以区分jqdata错误（可重试）、请求参数错误（400）等失败类别
 #[derive(Debug)]
-pub struct Error(pub String);
+pub enum Error {
+    Db(rusqlite::Error),
+    Parse(chrono::ParseError),
+    Jq(jqdata::Error),
+    BadRequest(String),
+    Internal(String),
+}
+
+impl Error {
+    /// 构造一个由调用方直接描述的错误，`kind`决定归入`BadRequest`还是`Internal`变体
+    pub fn custom(kind: ErrorKind, msg: String) -> Error {
+        match kind {
+            ErrorKind::BadRequest | ErrorKind::NotFound => Error::BadRequest(msg),
+            ErrorKind::InternalServerError => Error::Internal(msg),
+        }
+    }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{}", self.0))
+    /// 该错误是否可重试：所有jqdata错误（如配额限制）一律视为可重试，
+    /// 不区分具体原因；数据库、解析及自定义错误均不可重试
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::Jq(_))
     }
 }
 
-impl std::error::Error for Error {}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Db(e) => write!(f, "database error: {}", e),
+            Error::Parse(e) => write!(f, "parse error: {}", e),
+            Error::Jq(e) => write!(f, "jqdata error: {}", e),
+            Error::BadRequest(msg) => write!(f, "bad request: {}", msg),
+            Error::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Db(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Jq(e) => Some(e),
+            Error::BadRequest(_) | Error::Internal(_) => None,
+        }
+    }
+}
 
 impl From<rusqlite::Error> for Error {
     fn from(err: rusqlite::Error) -> Error {
-        Error(format!("{}", err))
+        Error::Db(err)
     }
 }
 
 impl From<chrono::ParseError> for Error {
     fn from(err: chrono::ParseError) -> Error {
-        Error(format!("{}", err))
+        Error::Parse(err)
     }
 }
 
 impl From<jqdata::Error> for Error {
     fn from(err: jqdata::Error) -> Error {
-        Error(format!("{}", err))
+        Error::Jq(err)
     }
-}
\ No newline at end of file
+}
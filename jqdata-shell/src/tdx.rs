@@ -0,0 +1,189 @@
+use crate::error::Error;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const RECORD_LEN: usize = 32;
+
+/// 单条本地日线行情，字段含义与行情接口返回的 tick price 一致，
+/// 便于 `TdxDaySource` 与 `JqdataClient` 互为替代的数据源
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayBar {
+    pub code: String,
+    pub tick: String,
+    pub ts: NaiveDateTime,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    pub volume: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+/// 通达信 `.day` 本地日线数据源配置：股票代码到文件路径的映射
+#[derive(Debug, Clone, Default)]
+pub struct TdxDayConfig {
+    pub paths: HashMap<String, PathBuf>,
+}
+
+impl TdxDayConfig {
+    pub fn new() -> Self {
+        TdxDayConfig {
+            paths: HashMap::new(),
+        }
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.paths.insert(code.into(), path.into());
+        self
+    }
+}
+
+/// 直接读取通达信 `.day` 本地二进制文件的数据源，
+/// 使 MACD/ATR 计算管线可以完全脱离网络与 jqdata 配额限制
+pub struct TdxDaySource {
+    cfg: TdxDayConfig,
+}
+
+impl TdxDaySource {
+    pub fn new(cfg: TdxDayConfig) -> Self {
+        TdxDaySource { cfg }
+    }
+
+    /// 读取指定股票代码对应的 `.day` 文件，解析为按时间升序排列的日线序列
+    pub fn get_day_bars(&self, code: &str) -> Result<Vec<DayBar>> {
+        let path = self
+            .cfg
+            .paths
+            .get(code)
+            .ok_or_else(|| Error::BadRequest(format!("no .day file configured for code: {}", code)))?;
+
+        let mut buf = Vec::new();
+        File::open(path)
+            .map_err(|e| Error::Internal(format!("failed to open {}: {}", path.display(), e)))?
+            .read_to_end(&mut buf)
+            .map_err(|e| Error::Internal(format!("failed to read {}: {}", path.display(), e)))?;
+
+        if buf.len() % RECORD_LEN != 0 {
+            return Err(Error::Internal(format!(
+                "{} has unexpected length {}, not a multiple of the {}-byte record size",
+                path.display(),
+                buf.len(),
+                RECORD_LEN
+            )));
+        }
+
+        let mut bars = buf
+            .chunks_exact(RECORD_LEN)
+            .map(|rec| parse_record(code, rec))
+            .collect::<Result<Vec<DayBar>>>()?;
+        bars.sort_by_key(|b| b.ts);
+        Ok(bars)
+    }
+}
+
+fn parse_record(code: &str, rec: &[u8]) -> Result<DayBar> {
+    let date = le_u32(rec, 0);
+    let open = le_i32(rec, 4);
+    let high = le_i32(rec, 8);
+    let low = le_i32(rec, 12);
+    let close = le_i32(rec, 16);
+    let amount = le_f32(rec, 20);
+    let volume = le_i32(rec, 24);
+
+    // 日期以 yyyyMMdd 的压缩十进制形式存储
+    let year = (date / 10000) as i32;
+    let month = (date / 100 % 100) as u32;
+    let day = (date % 100) as u32;
+    let ts = NaiveDate::from_ymd_opt(year, month, day)
+        .ok_or_else(|| Error::Internal(format!("invalid packed date: {}", date)))?
+        .and_hms(0, 0, 0);
+
+    Ok(DayBar {
+        code: code.to_owned(),
+        tick: "1d".to_owned(),
+        ts,
+        open: price_decimal(open),
+        high: price_decimal(high),
+        low: price_decimal(low),
+        close: price_decimal(close),
+        volume: BigDecimal::from(volume),
+        amount: BigDecimal::from(amount as f64),
+    })
+}
+
+/// 价格字段以 0.01 元为单位存储，转换为元
+fn price_decimal(hundredths: i32) -> BigDecimal {
+    BigDecimal::from(hundredths) / BigDecimal::from(100)
+}
+
+fn le_u32(rec: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        rec[offset],
+        rec[offset + 1],
+        rec[offset + 2],
+        rec[offset + 3],
+    ])
+}
+
+fn le_i32(rec: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes([
+        rec[offset],
+        rec[offset + 1],
+        rec[offset + 2],
+        rec[offset + 3],
+    ])
+}
+
+fn le_f32(rec: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes([
+        rec[offset],
+        rec[offset + 1],
+        rec[offset + 2],
+        rec[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: u32, open: i32, high: i32, low: i32, close: i32, amount: f32, volume: i32) -> Vec<u8> {
+        let mut rec = Vec::with_capacity(RECORD_LEN);
+        rec.extend_from_slice(&date.to_le_bytes());
+        rec.extend_from_slice(&open.to_le_bytes());
+        rec.extend_from_slice(&high.to_le_bytes());
+        rec.extend_from_slice(&low.to_le_bytes());
+        rec.extend_from_slice(&close.to_le_bytes());
+        rec.extend_from_slice(&amount.to_le_bytes());
+        rec.extend_from_slice(&volume.to_le_bytes());
+        rec.extend_from_slice(&[0u8; 4]);
+        rec
+    }
+
+    #[test]
+    fn test_parse_record() -> Result<()> {
+        let rec = record(20200201, 1000, 1050, 990, 1020, 123456.0, 7890);
+        let bar = parse_record("000001", &rec)?;
+        assert_eq!("000001", bar.code);
+        assert_eq!("1d", bar.tick);
+        assert_eq!(BigDecimal::from(10), bar.open);
+        assert_eq!(BigDecimal::from(10.50), bar.high);
+        assert_eq!(BigDecimal::from(9.90), bar.low);
+        assert_eq!(BigDecimal::from(10.20), bar.close);
+        assert_eq!(BigDecimal::from(7890), bar.volume);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_day_bars_missing_code() {
+        let source = TdxDaySource::new(TdxDayConfig::new());
+        assert!(source.get_day_bars("000001").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::stroke::Stroke;
+use crate::Result;
+use bigdecimal::BigDecimal;
+
+/// 将笔序列解析为线段序列
+pub fn strokes_to_segments(strokes: &[Stroke]) -> Result<Vec<Segment>> {
+    SegmentShaper::new(strokes).run()
+}
+
+/// 线段：由一组连续的笔构成，方向与首笔一致
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: Stroke,
+    pub end: Stroke,
+    pub upward: bool,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub n: u32,
+}
+
+pub struct SegmentShaper<'s> {
+    strokes: &'s [Stroke],
+}
+
+impl<'s> SegmentShaper<'s> {
+    pub fn new(strokes: &'s [Stroke]) -> Self {
+        SegmentShaper { strokes }
+    }
+
+    /// 按特征序列的缺口/重叠判断合并笔为线段
+    ///
+    /// 同向的两笔（跳过中间反向的一笔）若价格区间不再重叠，
+    /// 说明特征序列出现缺口，标志此前的线段在此终结
+    pub fn run(self) -> Result<Vec<Segment>> {
+        let mut segments = Vec::new();
+        if self.strokes.len() < 3 {
+            return Ok(segments);
+        }
+
+        let mut seg_start_idx = 0usize;
+        let mut idx = 0usize;
+        while idx + 2 < self.strokes.len() {
+            let a = &self.strokes[idx];
+            let b = &self.strokes[idx + 1];
+            let c = &self.strokes[idx + 2];
+            if a.upward == c.upward && b.upward != a.upward && !overlaps(a, c) {
+                if let Some(seg) = build_segment(&self.strokes[seg_start_idx..=idx + 1]) {
+                    segments.push(seg);
+                }
+                seg_start_idx = idx + 1;
+            }
+            idx += 1;
+        }
+
+        // 末尾剩余的笔若不足3笔，无法像前面一样通过缺口测试确认线段的存在，
+        // 因此不作为线段输出，留待后续笔到来时再判断（避免把刚闭合线段的
+        // 共用笔错误地当作新线段重复输出）
+        if self.strokes.len() - seg_start_idx >= 3 {
+            if let Some(seg) = build_segment(&self.strokes[seg_start_idx..]) {
+                segments.push(seg);
+            }
+        }
+
+        Ok(segments)
+    }
+}
+
+fn overlaps(a: &Stroke, b: &Stroke) -> bool {
+    a.low <= b.high && b.low <= a.high
+}
+
+fn build_segment(strokes: &[Stroke]) -> Option<Segment> {
+    let first = strokes.first()?;
+    let last = strokes.last()?;
+    let high = strokes.iter().map(|s| s.high.clone()).max()?;
+    let low = strokes.iter().map(|s| s.low.clone()).min()?;
+    let n = strokes.iter().map(|s| s.n).sum();
+    Some(Segment {
+        start: first.clone(),
+        end: last.clone(),
+        upward: first.upward,
+        high,
+        low,
+        n,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Parting;
+    use chrono::NaiveDateTime;
+
+    fn new_pt(ts: &str, extremum_price: f64, top: bool) -> Parting {
+        Parting {
+            start_ts: new_ts(ts),
+            end_ts: new_ts(ts),
+            extremum_ts: new_ts(ts),
+            extremum_price: BigDecimal::from(extremum_price),
+            n: 3,
+            top,
+        }
+    }
+
+    fn new_ts(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn new_stroke(start_ts: &str, end_ts: &str, high: f64, low: f64, upward: bool) -> Stroke {
+        Stroke {
+            start: new_pt(start_ts, if upward { low } else { high }, !upward),
+            end: new_pt(end_ts, if upward { high } else { low }, upward),
+            upward,
+            high: BigDecimal::from(high),
+            low: BigDecimal::from(low),
+            n: 10,
+        }
+    }
+
+    #[test]
+    fn test_no_segment_with_too_few_strokes() -> Result<()> {
+        let strokes = vec![
+            new_stroke("2020-02-01 10:00", "2020-02-01 10:10", 10.50, 10.00, true),
+            new_stroke("2020-02-01 10:10", "2020-02-01 10:20", 10.30, 10.10, false),
+        ];
+        let r = strokes_to_segments(&strokes)?;
+        assert_eq!(0, r.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_segment_breaks_on_gap() -> Result<()> {
+        let strokes = vec![
+            new_stroke("2020-02-01 10:00", "2020-02-01 10:10", 10.50, 10.00, true),
+            new_stroke("2020-02-01 10:10", "2020-02-01 10:20", 10.30, 10.10, false),
+            new_stroke("2020-02-01 10:20", "2020-02-01 10:30", 11.50, 11.00, true),
+        ];
+        let r = strokes_to_segments(&strokes)?;
+        assert_eq!(1, r.len());
+        assert_eq!(true, r[0].upward);
+        assert_eq!(BigDecimal::from(10.50), r[0].high);
+        Ok(())
+    }
+}
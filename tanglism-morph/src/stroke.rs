@@ -0,0 +1,263 @@
+use crate::shape::{Parting, K};
+use crate::Result;
+use bigdecimal::BigDecimal;
+
+/// 将K线与分型序列解析为笔序列，`ks`须为生成`pts`所用的同一份原始K线切片，
+/// 用于准确统计两个分型极值之间的独立K线数
+pub fn pts_to_strokes(ks: &[K], pts: &[Parting]) -> Result<Vec<Stroke>> {
+    StrokeShaper::new(ks, pts, StrokeConfig::default()).run()
+}
+
+/// 笔的构造配置
+#[derive(Debug, Clone)]
+pub struct StrokeConfig {
+    /// 构成一笔所需的两个分型极值之间的最小独立K线数
+    pub min_k_gap: u32,
+}
+
+impl Default for StrokeConfig {
+    fn default() -> Self {
+        // 非包含处理规则下为5，包含处理规则下为4
+        StrokeConfig { min_k_gap: 5 }
+    }
+}
+
+impl StrokeConfig {
+    pub fn for_inclusive_k(inclusive_k: bool) -> Self {
+        StrokeConfig {
+            min_k_gap: if inclusive_k { 4 } else { 5 },
+        }
+    }
+}
+
+/// 笔：连接一个顶分型与随后的一个底分型（或相反）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub start: Parting,
+    pub end: Parting,
+    pub upward: bool,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub n: u32,
+}
+
+pub struct StrokeShaper<'k, 'p> {
+    ks: &'k [K],
+    pts: &'p [Parting],
+    cfg: StrokeConfig,
+}
+
+impl<'k, 'p> StrokeShaper<'k, 'p> {
+    pub fn new(ks: &'k [K], pts: &'p [Parting], cfg: StrokeConfig) -> Self {
+        StrokeShaper { ks, pts, cfg }
+    }
+
+    pub fn run(self) -> Result<Vec<Stroke>> {
+        // 相邻同向分型折叠，只保留极值更突出的一个，确保分型序列顶底交替
+        let collapsed = collapse_same_direction(self.pts);
+
+        let mut strokes = Vec::new();
+        let mut start = match collapsed.first() {
+            Some(p) => p.clone(),
+            None => return Ok(strokes),
+        };
+        for end in collapsed.iter().skip(1) {
+            if end.top == start.top {
+                // 同类型分型出现在当前起点之后（常见于中间分型因与起点靠得太近
+                // 而被跳过），此时若它比当前起点更极端，应取而代之，
+                // 否则起点会永久停留在一个已被后来者超越的分型上
+                let more_extreme = if end.top {
+                    end.extremum_price > start.extremum_price
+                } else {
+                    end.extremum_price < start.extremum_price
+                };
+                if more_extreme {
+                    start = end.clone();
+                }
+                continue;
+            }
+            let gap = independent_k_gap(self.ks, &start, end);
+            if gap < self.cfg.min_k_gap {
+                // 两分型靠得太近，尚不能构成有效的一笔，跳过该分型继续向后寻找
+                continue;
+            }
+            let upward = !start.top;
+            let (high, low) = if upward {
+                (end.extremum_price.clone(), start.extremum_price.clone())
+            } else {
+                (start.extremum_price.clone(), end.extremum_price.clone())
+            };
+            strokes.push(Stroke {
+                start: start.clone(),
+                end: end.clone(),
+                upward,
+                high,
+                low,
+                n: gap,
+            });
+            start = end.clone();
+        }
+        Ok(strokes)
+    }
+}
+
+/// 相邻同向分型折叠：保留极值更突出的分型
+fn collapse_same_direction(pts: &[Parting]) -> Vec<Parting> {
+    let mut out: Vec<Parting> = Vec::with_capacity(pts.len());
+    for p in pts {
+        match out.last_mut() {
+            Some(last) if last.top == p.top => {
+                let replace = if p.top {
+                    p.extremum_price > last.extremum_price
+                } else {
+                    p.extremum_price < last.extremum_price
+                };
+                if replace {
+                    *last = p.clone();
+                }
+            }
+            _ => out.push(p.clone()),
+        }
+    }
+    out
+}
+
+/// 两个分型极值之间的独立K线数：统计`ks`中严格落在两个`extremum_ts`之间的K线根数
+fn independent_k_gap(ks: &[K], start: &Parting, end: &Parting) -> u32 {
+    let (from, to) = if start.extremum_ts <= end.extremum_ts {
+        (start.extremum_ts, end.extremum_ts)
+    } else {
+        (end.extremum_ts, start.extremum_ts)
+    };
+    ks.iter()
+        .filter(|k| k.ts > from && k.ts < to)
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parting::ks_to_pts;
+    use chrono::NaiveDateTime;
+
+    fn new_pt(start_ts: &str, extremum_ts: &str, end_ts: &str, extremum_price: f64, n: u32, top: bool) -> Parting {
+        Parting {
+            start_ts: new_ts(start_ts),
+            end_ts: new_ts(end_ts),
+            extremum_ts: new_ts(extremum_ts),
+            extremum_price: BigDecimal::from(extremum_price),
+            n,
+            top,
+        }
+    }
+
+    fn new_ts(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn new_k(ts: &str, high: f64, low: f64) -> K {
+        K {
+            ts: new_ts(ts),
+            high: BigDecimal::from(high),
+            low: BigDecimal::from(low),
+        }
+    }
+
+    /// 生成从`from`起每分钟一根、覆盖`minutes`根的K线，高低点数值仅作占位，
+    /// 供只关心独立K线计数的测试使用
+    fn dense_ks(from_minute: u32, minutes: u32) -> Vec<K> {
+        (0..minutes)
+            .map(|i| {
+                let ts = format!("2020-02-01 10:{:02}", from_minute + i);
+                new_k(&ts, 10.00, 9.00)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_no_strokes_from_single_parting() -> Result<()> {
+        let pts = vec![new_pt(
+            "2020-02-01 10:00",
+            "2020-02-01 10:02",
+            "2020-02-01 10:04",
+            10.20,
+            3,
+            true,
+        )];
+        let r = pts_to_strokes(&[], &pts)?;
+        assert_eq!(0, r.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_stroke_between_top_and_bottom() -> Result<()> {
+        // 先涨后跌再反弹，中间无二次反复，真实的PartingShaper应恰好产出一顶一底
+        let ks = vec![
+            new_k("2020-02-01 10:00", 10.30, 10.20),
+            new_k("2020-02-01 10:01", 10.35, 10.25),
+            new_k("2020-02-01 10:02", 10.40, 10.30),
+            new_k("2020-02-01 10:03", 10.35, 10.25),
+            new_k("2020-02-01 10:04", 10.30, 10.20),
+            new_k("2020-02-01 10:05", 10.25, 10.15),
+            new_k("2020-02-01 10:06", 10.20, 10.10),
+            new_k("2020-02-01 10:07", 10.15, 10.05),
+            new_k("2020-02-01 10:08", 10.10, 10.00),
+            new_k("2020-02-01 10:09", 10.05, 9.95),
+            new_k("2020-02-01 10:10", 10.10, 10.00),
+        ];
+        let pts = ks_to_pts(&ks)?;
+        assert_eq!(2, pts.len());
+
+        let r = pts_to_strokes(&ks, &pts)?;
+        assert_eq!(1, r.len());
+        assert_eq!(false, r[0].upward);
+        assert_eq!(BigDecimal::from(10.40), r[0].high);
+        assert_eq!(BigDecimal::from(9.95), r[0].low);
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_direction_partings_collapse() -> Result<()> {
+        let pts = vec![
+            new_pt("2020-02-01 10:00", "2020-02-01 10:02", "2020-02-01 10:04", 10.20, 3, true),
+            new_pt("2020-02-01 10:05", "2020-02-01 10:06", "2020-02-01 10:07", 10.30, 3, true),
+            new_pt("2020-02-01 10:10", "2020-02-01 10:12", "2020-02-01 10:14", 9.80, 3, false),
+        ];
+        let ks = dense_ks(0, 15);
+        let r = pts_to_strokes(&ks, &pts)?;
+        assert_eq!(1, r.len());
+        assert_eq!(BigDecimal::from(10.30), r[0].high);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dominant_parting_supersedes_start_after_rejected_pair() -> Result<()> {
+        // T0与B1靠得太近被跳过；T2同为顶分型但比T0更高，应取而代之成为新起点，
+        // 否则B3会与已被超越的T0错误地连成一笔
+        let pts = vec![
+            new_pt("2020-02-01 10:00", "2020-02-01 10:02", "2020-02-01 10:04", 10.20, 3, true),
+            new_pt("2020-02-01 10:05", "2020-02-01 10:06", "2020-02-01 10:07", 10.15, 3, false),
+            new_pt("2020-02-01 10:18", "2020-02-01 10:20", "2020-02-01 10:22", 10.30, 3, true),
+            new_pt("2020-02-01 10:38", "2020-02-01 10:40", "2020-02-01 10:42", 9.80, 3, false),
+        ];
+        let ks = dense_ks(0, 46);
+        let r = pts_to_strokes(&ks, &pts)?;
+        assert_eq!(1, r.len());
+        assert_eq!(false, r[0].upward);
+        assert_eq!(BigDecimal::from(10.30), r[0].high);
+        assert_eq!(BigDecimal::from(9.80), r[0].low);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stroke_rejected_when_too_close() -> Result<()> {
+        let pts = vec![
+            new_pt("2020-02-01 10:00", "2020-02-01 10:02", "2020-02-01 10:04", 10.20, 3, true),
+            new_pt("2020-02-01 10:05", "2020-02-01 10:06", "2020-02-01 10:07", 9.80, 3, false),
+        ];
+        let ks = dense_ks(0, 8);
+        let r = pts_to_strokes(&ks, &pts)?;
+        assert_eq!(0, r.len());
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
+pub mod adjustment;
 pub mod atr;
+pub mod divergence;
 mod ema;
+pub mod frame;
 mod ma;
+mod tdx_source;
 
 use super::stock_prices::get_stock_tick_prices;
 use crate::models::StockTickPrice;
@@ -8,11 +12,14 @@ use crate::BasicCfg;
 use crate::{DbPool, Error, ErrorKind, Result};
 use bigdecimal::BigDecimal;
 use chrono::{NaiveDate, NaiveDateTime};
+use adjustment::Adjustment;
 use ema::approximate_macd;
 use jqdata::JqdataClient;
+use jqdata_shell::tdx::TdxDaySource;
 use serde_derive::*;
 use std::collections::HashMap;
 use tanglism_utils::{TradingDates, LOCAL_DATES};
+use tdx_source::get_tdx_tick_prices;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response<T> {
@@ -82,6 +89,8 @@ pub struct MacdCfg {
     fast_ema_period: u32,
     slow_ema_period: u32,
     dea_period: u32,
+    #[serde(default)]
+    adjustment: Adjustment,
 }
 
 impl Default for MacdCfg {
@@ -90,6 +99,7 @@ impl Default for MacdCfg {
             fast_ema_period: 12,
             slow_ema_period: 26,
             dea_period: 9,
+            adjustment: Adjustment::default(),
         }
     }
 }
@@ -100,20 +110,12 @@ pub async fn get_metrics_macd(
     basic_cfg: BasicCfg,
     macd_cfg: MacdCfg,
 ) -> Result<MacdMetric> {
-    let fast_ema_period = macd_cfg.fast_ema_period;
-    let slow_ema_period = macd_cfg.slow_ema_period;
-    let dea_period = macd_cfg.dea_period;
-    if slow_ema_period < fast_ema_period || slow_ema_period < dea_period {
-        return Err(Error::custom(
-            ErrorKind::BadRequest,
-            format!(
-                "invalid setting: slow ema {} is no less than fast ema {} or dea {}",
-                slow_ema_period, fast_ema_period, dea_period
-            ),
-        ));
-    }
-    let search_start_dt =
-        ema_approximate_start(basic_cfg.start_ts.date(), &basic_cfg.tick, slow_ema_period)?;
+    validate_macd_cfg(&macd_cfg)?;
+    let search_start_dt = ema_approximate_start(
+        basic_cfg.start_ts.date(),
+        &basic_cfg.tick,
+        macd_cfg.slow_ema_period,
+    )?;
     let prices = get_stock_tick_prices(
         &db,
         &jq,
@@ -123,11 +125,62 @@ pub async fn get_metrics_macd(
         basic_cfg.end_ts,
     )
     .await?;
+    macd_from_prices(db, prices, &basic_cfg, &macd_cfg)
+}
+
+/// 以本地通达信 `.day` 文件作为行情来源的同款MACD管线，使计算可以完全脱离jqdata网络请求；
+/// 复权事件仍从数据库读取——除权除息是公司行为，与行情数据来源无关
+pub fn get_metrics_macd_from_tdx(
+    db: &DbPool,
+    tdx: &TdxDaySource,
+    basic_cfg: BasicCfg,
+    macd_cfg: MacdCfg,
+) -> Result<MacdMetric> {
+    validate_macd_cfg(&macd_cfg)?;
+    let search_start_dt = ema_approximate_start(
+        basic_cfg.start_ts.date(),
+        &basic_cfg.tick,
+        macd_cfg.slow_ema_period,
+    )?;
+    let prices = get_tdx_tick_prices(
+        tdx,
+        &basic_cfg.code,
+        search_start_dt.and_hms(0, 0, 0),
+        basic_cfg.end_ts,
+    )?;
+    macd_from_prices(db, prices, &basic_cfg, &macd_cfg)
+}
+
+fn validate_macd_cfg(macd_cfg: &MacdCfg) -> Result<()> {
+    if macd_cfg.slow_ema_period < macd_cfg.fast_ema_period
+        || macd_cfg.slow_ema_period < macd_cfg.dea_period
+    {
+        return Err(Error::custom(
+            ErrorKind::BadRequest,
+            format!(
+                "invalid setting: slow ema {} is no less than fast ema {} or dea {}",
+                macd_cfg.slow_ema_period, macd_cfg.fast_ema_period, macd_cfg.dea_period
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// 给定已取得的原始行情序列，完成复权并求出MACD三线，与行情来源（jqdata或本地tdx）无关
+fn macd_from_prices(
+    db: &DbPool,
+    mut prices: Vec<StockTickPrice>,
+    basic_cfg: &BasicCfg,
+    macd_cfg: &MacdCfg,
+) -> Result<MacdMetric> {
+    // 在计算EMA/MACD之前完成复权，避免除权除息造成的价格跳空干扰长窗口均线
+    let ex_rights_events = adjustment::load_ex_rights_events(db, &basic_cfg.code)?;
+    adjustment::adjust(&mut prices, &ex_rights_events, macd_cfg.adjustment)?;
     let (dif_raw, dea_raw, macd_raw) = approximate_macd(
         &prices,
-        fast_ema_period,
-        slow_ema_period,
-        dea_period,
+        macd_cfg.fast_ema_period,
+        macd_cfg.slow_ema_period,
+        macd_cfg.dea_period,
         |p| p.close.clone(),
         |p| p.ts,
     );
@@ -144,9 +197,9 @@ pub async fn get_metrics_macd(
         .filter(|d| d.ts >= basic_cfg.start_ts)
         .collect();
     Ok(MacdMetric {
-        fast_ema_period,
-        slow_ema_period,
-        dea_period,
+        fast_ema_period: macd_cfg.fast_ema_period,
+        slow_ema_period: macd_cfg.slow_ema_period,
+        dea_period: macd_cfg.dea_period,
         dif,
         dea,
         macd,
@@ -157,6 +210,7 @@ pub fn parse_macd_cfg(s: &str) -> Option<MacdCfg> {
     let mut fast_ema_period = None;
     let mut slow_ema_period = None;
     let mut dea_period = None;
+    let mut adjustment = Adjustment::default();
     for c in s.split(',') {
         if c.starts_with("fast_ema:") {
             if let Ok(n) = c[9..].parse() {
@@ -170,6 +224,12 @@ pub fn parse_macd_cfg(s: &str) -> Option<MacdCfg> {
             if let Ok(n) = c[4..].parse() {
                 dea_period = Some(n);
             }
+        } else if c.starts_with("adj:") {
+            adjustment = match &c[4..] {
+                "forward" => Adjustment::Forward,
+                "backward" => Adjustment::Backward,
+                _ => Adjustment::None,
+            };
         }
     }
     match (fast_ema_period, slow_ema_period, dea_period) {
@@ -177,12 +237,13 @@ pub fn parse_macd_cfg(s: &str) -> Option<MacdCfg> {
             fast_ema_period,
             slow_ema_period,
             dea_period,
+            adjustment,
         }),
         _ => None,
     }
 }
 
-fn ema_approximate_start(start_dt: NaiveDate, tick: &str, period: u32) -> Result<NaiveDate> {
+pub(super) fn ema_approximate_start(start_dt: NaiveDate, tick: &str, period: u32) -> Result<NaiveDate> {
     // 计算额外所需的价格序列的起始区间
     // 3.5 * 周期，之前的价格影响很小
     let total_period = (3.50_f64 * period as f64) as i64;
@@ -0,0 +1,185 @@
+use crate::models::StockTickPrice;
+use crate::{DbPool, Error, ErrorKind, Result};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde_derive::*;
+use std::str::FromStr;
+
+/// 价格复权模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Adjustment {
+    /// 不复权
+    None,
+    /// 前复权：以最新一根K线为基准，历史价格按累计除权系数缩放
+    Forward,
+    /// 后复权：以最早一根K线为基准
+    Backward,
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Adjustment::None
+    }
+}
+
+/// 单次除权除息事件：事件发生日及当日的调整系数
+#[derive(Debug, Clone)]
+pub struct ExRightsEvent {
+    pub ex_date: NaiveDate,
+    pub factor: BigDecimal,
+}
+
+/// 从数据库读取某股票全部历史除权事件，按 `ex_date` 升序排列
+pub fn load_ex_rights_events(db: &DbPool, code: &str) -> Result<Vec<ExRightsEvent>> {
+    let conn = db.get().map_err(|e| {
+        Error::custom(
+            ErrorKind::InternalServerError,
+            format!("failed to get db connection: {}", e),
+        )
+    })?;
+    let mut stmt = conn.prepare("SELECT ex_date, factor FROM ex_rights WHERE code = ?1 ORDER BY ex_date ASC")?;
+    let rows = stmt.query_map(rusqlite::params![code], |row| {
+        let ex_date: String = row.get(0)?;
+        let factor: String = row.get(1)?;
+        Ok((ex_date, factor))
+    })?;
+    let mut events = Vec::new();
+    for row in rows {
+        let (ex_date, factor) = row?;
+        let ex_date = NaiveDate::parse_from_str(&ex_date, "%Y-%m-%d")?;
+        let factor = BigDecimal::from_str(&factor).map_err(|e| {
+            Error::custom(
+                ErrorKind::BadRequest,
+                format!("invalid ex-rights factor {}: {}", factor, e),
+            )
+        })?;
+        events.push(ExRightsEvent { ex_date, factor });
+    }
+    Ok(events)
+}
+
+/// 截至给定日期（含）为止的累计除权系数，`cum_factors` 须已按 `ex_date` 升序排列
+///
+/// 若日期早于最早一条除权事件，说明该日的累计系数未知：调用方不能假设为1，
+/// 因为上市日的系数本身可能就不是1（常见于不同面值或历史拆分未完整记录的情况）。
+/// 这种情况下返回 `None`，由调用方显式报错而非悄悄当作恒等变换处理。
+fn cum_factor_at(cum_factors: &[(NaiveDate, BigDecimal)], date: NaiveDate) -> Option<BigDecimal> {
+    cum_factors
+        .iter()
+        .rev()
+        .find(|(ex_date, _)| *ex_date <= date)
+        .map(|(_, f)| f.clone())
+}
+
+/// 对给定价格序列应用复权，序列须已按 `ts` 升序排列
+///
+/// `events` 为该股票全部历史除权事件。若任何一根K线的日期早于最早一条除权事件，
+/// 返回错误：此时无法确定该日期的累计系数，不得默认其为1。
+pub fn adjust(prices: &mut [StockTickPrice], events: &[ExRightsEvent], adjustment: Adjustment) -> Result<()> {
+    if adjustment == Adjustment::None || events.is_empty() || prices.is_empty() {
+        return Ok(());
+    }
+
+    let mut acc = BigDecimal::from(1);
+    let cum_factors: Vec<(NaiveDate, BigDecimal)> = events
+        .iter()
+        .map(|e| {
+            acc = &acc * &e.factor;
+            (e.ex_date, acc.clone())
+        })
+        .collect();
+    let earliest_event_date = events[0].ex_date;
+
+    let factor_at = |date: NaiveDate| -> Result<BigDecimal> {
+        cum_factor_at(&cum_factors, date).ok_or_else(|| {
+            Error::custom(
+                ErrorKind::BadRequest,
+                format!(
+                    "price at {} predates the earliest known ex-rights event ({}); \
+                     the listing-day factor cannot be assumed to be 1",
+                    date, earliest_event_date
+                ),
+            )
+        })
+    };
+
+    let anchor_date = match adjustment {
+        Adjustment::Forward => prices[prices.len() - 1].ts.date(),
+        Adjustment::Backward => prices[0].ts.date(),
+        Adjustment::None => return Ok(()),
+    };
+    let anchor = factor_at(anchor_date)?;
+
+    for p in prices.iter_mut() {
+        let factor = &factor_at(p.ts.date())? / &anchor;
+        p.open = &p.open * &factor;
+        p.high = &p.high * &factor;
+        p.low = &p.low * &factor;
+        p.close = &p.close * &factor;
+        p.volume = &p.volume / &factor;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn new_price(ts: &str, close: f64) -> StockTickPrice {
+        let ts = chrono::NaiveDateTime::parse_from_str(&format!("{} 00:00", ts), "%Y-%m-%d %H:%M").unwrap();
+        StockTickPrice {
+            tick: "1d".to_owned(),
+            code: "000001".to_owned(),
+            ts,
+            open: BigDecimal::from(close),
+            high: BigDecimal::from(close),
+            low: BigDecimal::from(close),
+            close: BigDecimal::from(close),
+            volume: BigDecimal::from(100),
+            amount: BigDecimal::from(close * 100.0),
+        }
+    }
+
+    fn new_event(ex_date: &str, factor: f64) -> ExRightsEvent {
+        ExRightsEvent {
+            ex_date: NaiveDate::parse_from_str(ex_date, "%Y-%m-%d").unwrap(),
+            factor: BigDecimal::from(factor),
+        }
+    }
+
+    #[test]
+    fn test_forward_adjustment_anchors_on_latest_bar() -> Result<()> {
+        let mut prices = vec![
+            new_price("2020-01-01", 10.0),
+            new_price("2020-02-01", 10.0),
+            new_price("2020-03-01", 20.0),
+        ];
+        // 2020-01-01 上市，系数1；2020-02-01 除权，系数2（例如10送10，股本翻倍）
+        let events = vec![new_event("2020-01-01", 1.0), new_event("2020-02-01", 2.0)];
+        adjust(&mut prices, &events, Adjustment::Forward)?;
+        // 最新一根不变
+        assert_eq!(BigDecimal::from(20.0), prices[2].close);
+        // 除权日之前的历史价格按 1/2 缩放
+        assert_eq!(BigDecimal::from(5.0), prices[0].close);
+        // 除权日当天的K线已是除权后价格，与最新一根累计系数相同，不变
+        assert_eq!(BigDecimal::from(10.0), prices[1].close);
+        Ok(())
+    }
+
+    #[test]
+    fn test_adjust_rejects_price_predating_first_event() {
+        let mut prices = vec![new_price("2019-12-01", 10.0), new_price("2020-03-01", 20.0)];
+        let events = vec![new_event("2020-02-01", 0.5)];
+        let result = adjust(&mut prices, &events, Adjustment::Forward);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_adjust_noop_without_events() -> Result<()> {
+        let mut prices = vec![new_price("2020-01-01", 10.0)];
+        adjust(&mut prices, &[], Adjustment::Forward)?;
+        assert_eq!(BigDecimal::from(10.0), prices[0].close);
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use super::Metric;
+use super::MacdMetric;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDateTime;
+use tanglism_morph::stroke::Stroke;
+
+/// 背驰类型：顶背驰或底背驰
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Top,
+    Bottom,
+}
+
+/// 背驰：同向的两笔中，后一笔价格创新高/新低，但MACD黄白面积与DIF峰值却未同步放大
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub kind: Kind,
+    pub first_leg: Stroke,
+    pub second_leg: Stroke,
+    /// 第二笔面积 / 第一笔面积
+    pub area_ratio: BigDecimal,
+    /// 第二笔DIF峰值 / 第一笔DIF峰值
+    pub dif_ratio: BigDecimal,
+}
+
+/// 结合笔结构与MACD指标检测背驰
+///
+/// 对每一对被一笔回调分隔开的同向笔（如两段上涨笔），比较二者的MACD黄白面积与DIF峰值：
+/// 若后一笔价格创出更极端的高/低点，但面积与DIF峰值反而更小，则判定为背驰。
+pub fn detect(strokes: &[Stroke], macd: &MacdMetric) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    if strokes.len() < 3 {
+        return divergences;
+    }
+
+    for w in strokes.windows(3) {
+        let (first_leg, pullback, second_leg) = (&w[0], &w[1], &w[2]);
+        if first_leg.upward != second_leg.upward || pullback.upward == first_leg.upward {
+            continue;
+        }
+
+        let upward = first_leg.upward;
+        let first_area = leg_area(first_leg, macd, upward);
+        let second_area = leg_area(second_leg, macd, upward);
+        let first_dif_peak = leg_dif_peak(first_leg, macd, upward);
+        let second_dif_peak = leg_dif_peak(second_leg, macd, upward);
+
+        if first_area.is_zero() || first_dif_peak.is_zero() {
+            continue;
+        }
+
+        let price_extends = if upward {
+            second_leg.high > first_leg.high
+        } else {
+            second_leg.low < first_leg.low
+        };
+        if !price_extends {
+            continue;
+        }
+
+        let area_ratio = &second_area / &first_area;
+        let dif_ratio = &second_dif_peak / &first_dif_peak;
+        if area_ratio < BigDecimal::from(1) && dif_ratio < BigDecimal::from(1) {
+            divergences.push(Divergence {
+                kind: if upward { Kind::Top } else { Kind::Bottom },
+                first_leg: first_leg.clone(),
+                second_leg: second_leg.clone(),
+                area_ratio,
+                dif_ratio,
+            });
+        }
+    }
+
+    divergences
+}
+
+/// 一笔的时间跨度，以起止分型的极值时间为界
+fn leg_span(leg: &Stroke) -> (NaiveDateTime, NaiveDateTime) {
+    let a = leg.start.extremum_ts;
+    let b = leg.end.extremum_ts;
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// 在给定时间点取指标值：若无精确采样，按前后最近的两个采样点线性插值；
+/// 若该时间点落在全部采样范围之外，则夹逼（clamp）到最近的边界采样
+///
+/// `values` 须已按 `ts` 升序排列
+fn interpolate_at(values: &[Metric], ts: NaiveDateTime) -> Option<BigDecimal> {
+    if values.is_empty() {
+        return None;
+    }
+    if let Ok(idx) = values.binary_search_by_key(&ts, |m| m.ts) {
+        return Some(values[idx].value.clone());
+    }
+    let idx = values.partition_point(|m| m.ts < ts);
+    if idx == 0 {
+        return Some(values[0].value.clone());
+    }
+    if idx == values.len() {
+        return Some(values[values.len() - 1].value.clone());
+    }
+    let before = &values[idx - 1];
+    let after = &values[idx];
+    let span = (after.ts - before.ts).num_milliseconds();
+    if span == 0 {
+        return Some(before.value.clone());
+    }
+    let offset = (ts - before.ts).num_milliseconds();
+    let ratio = BigDecimal::from(offset) / BigDecimal::from(span);
+    Some(&before.value + &(&(&after.value - &before.value) * &ratio))
+}
+
+/// 取跨度内的指标序列，跨度两端若未精确落在采样点上，通过插值/夹逼补齐边界值，
+/// 再拼接上严格落在区间内部的原始采样
+fn span_values(values: &[Metric], start: NaiveDateTime, end: NaiveDateTime) -> Vec<BigDecimal> {
+    let mut out = Vec::new();
+    if let Some(v) = interpolate_at(values, start) {
+        out.push(v);
+    }
+    out.extend(
+        values
+            .iter()
+            .filter(|m| m.ts > start && m.ts < end)
+            .map(|m| m.value.clone()),
+    );
+    if end > start {
+        if let Some(v) = interpolate_at(values, end) {
+            out.push(v);
+        }
+    }
+    out
+}
+
+/// 一笔对应的MACD黄白面积：上涨笔取柱状值为正的部分求和，下跌笔取为负的部分取绝对值求和
+fn leg_area(leg: &Stroke, macd: &MacdMetric, upward: bool) -> BigDecimal {
+    let (start, end) = leg_span(leg);
+    span_values(&macd.macd, start, end)
+        .iter()
+        .fold(BigDecimal::zero(), |acc, v| {
+            if upward {
+                if v > &BigDecimal::zero() {
+                    acc + v
+                } else {
+                    acc
+                }
+            } else if v < &BigDecimal::zero() {
+                acc - v
+            } else {
+                acc
+            }
+        })
+}
+
+/// 一笔跨度内的DIF峰值：上涨笔取最大值，下跌笔取最小值的绝对值
+fn leg_dif_peak(leg: &Stroke, macd: &MacdMetric, upward: bool) -> BigDecimal {
+    let (start, end) = leg_span(leg);
+    let values = span_values(&macd.dif, start, end);
+    let mut iter = values.iter();
+    let first = match iter.next() {
+        Some(v) => v.clone(),
+        None => return BigDecimal::zero(),
+    };
+    let peak = iter.fold(first, |acc, v| {
+        if upward {
+            if v > &acc {
+                v.clone()
+            } else {
+                acc
+            }
+        } else if v < &acc {
+            v.clone()
+        } else {
+            acc
+        }
+    });
+    if upward {
+        peak
+    } else {
+        peak.abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tanglism_morph::shape::Parting;
+
+    fn new_ts(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn new_metric(ts: &str, value: f64) -> Metric {
+        Metric {
+            ts: new_ts(ts),
+            value: BigDecimal::from(value),
+        }
+    }
+
+    fn new_pt(ts: &str, extremum_price: f64, top: bool) -> Parting {
+        Parting {
+            start_ts: new_ts(ts),
+            end_ts: new_ts(ts),
+            extremum_ts: new_ts(ts),
+            extremum_price: BigDecimal::from(extremum_price),
+            n: 3,
+            top,
+        }
+    }
+
+    fn new_stroke(start_ts: &str, end_ts: &str, high: f64, low: f64, upward: bool) -> Stroke {
+        Stroke {
+            start: new_pt(start_ts, if upward { low } else { high }, !upward),
+            end: new_pt(end_ts, if upward { high } else { low }, upward),
+            upward,
+            high: BigDecimal::from(high),
+            low: BigDecimal::from(low),
+            n: 10,
+        }
+    }
+
+    #[test]
+    fn test_interpolate_at_exact_sample() {
+        let values = vec![new_metric("2020-02-01 10:00", 1.0), new_metric("2020-02-01 10:10", 2.0)];
+        let v = interpolate_at(&values, new_ts("2020-02-01 10:00")).unwrap();
+        assert_eq!(BigDecimal::from(1.0), v);
+    }
+
+    #[test]
+    fn test_interpolate_at_midpoint() {
+        let values = vec![new_metric("2020-02-01 10:00", 1.0), new_metric("2020-02-01 10:10", 2.0)];
+        let v = interpolate_at(&values, new_ts("2020-02-01 10:05")).unwrap();
+        assert_eq!(BigDecimal::from(1.5), v);
+    }
+
+    #[test]
+    fn test_interpolate_at_clamps_outside_range() {
+        let values = vec![new_metric("2020-02-01 10:00", 1.0), new_metric("2020-02-01 10:10", 2.0)];
+        assert_eq!(BigDecimal::from(1.0), interpolate_at(&values, new_ts("2020-02-01 09:00")).unwrap());
+        assert_eq!(BigDecimal::from(2.0), interpolate_at(&values, new_ts("2020-02-01 11:00")).unwrap());
+    }
+
+    #[test]
+    fn test_leg_area_interpolates_unaligned_boundary() {
+        // 笔的跨度边界（10:05）未落在MACD采样点（10:00/10:10）上
+        let leg = new_stroke("2020-02-01 10:05", "2020-02-01 10:10", 11.0, 10.0, true);
+        let macd = MacdMetric {
+            macd: vec![new_metric("2020-02-01 10:00", 0.0), new_metric("2020-02-01 10:10", 2.0)],
+            ..MacdMetric::default()
+        };
+        // 区间内插值后为 [1.0 (10:05插值), 2.0 (10:10)]
+        let area = leg_area(&leg, &macd, true);
+        assert_eq!(BigDecimal::from(3.0), area);
+    }
+
+    #[test]
+    fn test_detect_top_divergence() {
+        let first_leg = new_stroke("2020-02-01 10:00", "2020-02-01 10:10", 11.0, 10.0, true);
+        let pullback = new_stroke("2020-02-01 10:10", "2020-02-01 10:20", 10.8, 10.3, false);
+        let second_leg = new_stroke("2020-02-01 10:20", "2020-02-01 10:30", 12.0, 10.3, true);
+        let strokes = vec![first_leg, pullback, second_leg];
+        let macd = MacdMetric {
+            dif: vec![new_metric("2020-02-01 10:00", 1.0), new_metric("2020-02-01 10:10", 2.0), new_metric("2020-02-01 10:20", 1.5), new_metric("2020-02-01 10:30", 1.2)],
+            macd: vec![new_metric("2020-02-01 10:00", 1.0), new_metric("2020-02-01 10:10", 2.0), new_metric("2020-02-01 10:20", 1.0), new_metric("2020-02-01 10:30", 0.5)],
+            ..MacdMetric::default()
+        };
+        let divergences = detect(&strokes, &macd);
+        assert_eq!(1, divergences.len());
+        assert_eq!(Kind::Top, divergences[0].kind);
+    }
+}
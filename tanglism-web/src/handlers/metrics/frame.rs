@@ -0,0 +1,395 @@
+use super::super::stock_prices::get_stock_tick_prices;
+use crate::models::StockTickPrice;
+use crate::{DbPool, Error, ErrorKind, Result};
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDateTime;
+use jqdata::JqdataClient;
+use polars::prelude::*;
+use std::fs::File;
+use std::path::PathBuf;
+
+/// 批量指标计算所需的周期配置与缓存目录
+///
+/// 与 [`super::MacdCfg`] 的单股票计算相比，这里面向多股票批量场景，
+/// 通过 Polars 的 `LazyFrame` 管线一次性对多支股票求出指标列。
+///
+/// 注意：与 [`super::get_metrics_macd`] 不同，这里不应用 [`super::adjustment::Adjustment`]
+/// 复权——批量路径目前只读原始价格。同一支发生过除权除息的股票，批量路径与单股票路径
+/// 算出的指标值会不一致，在两条管线都要用到的场景下需要留意这一差异。
+#[derive(Debug, Clone)]
+pub struct FrameCfg {
+    pub fast_ema_period: u32,
+    pub slow_ema_period: u32,
+    pub dea_period: u32,
+    pub atr_period: u32,
+    pub cache_dir: PathBuf,
+}
+
+impl Default for FrameCfg {
+    fn default() -> Self {
+        FrameCfg {
+            fast_ema_period: 12,
+            slow_ema_period: 26,
+            dea_period: 9,
+            atr_period: 14,
+            cache_dir: PathBuf::from("cache/metrics"),
+        }
+    }
+}
+
+/// 将多支股票的行情序列转化为 Polars `DataFrame`
+///
+/// 列为 `code, ts, open, high, low, close, volume, amount`，`ts` 以毫秒时间戳存储。
+pub fn prices_to_frame(prices: &[StockTickPrice]) -> Result<DataFrame> {
+    let n = prices.len();
+    let mut code = Vec::with_capacity(n);
+    let mut ts = Vec::with_capacity(n);
+    let mut open = Vec::with_capacity(n);
+    let mut high = Vec::with_capacity(n);
+    let mut low = Vec::with_capacity(n);
+    let mut close = Vec::with_capacity(n);
+    let mut volume = Vec::with_capacity(n);
+    let mut amount = Vec::with_capacity(n);
+    for p in prices {
+        code.push(p.code.clone());
+        ts.push(p.ts.timestamp_millis());
+        open.push(to_f64(&p.open)?);
+        high.push(to_f64(&p.high)?);
+        low.push(to_f64(&p.low)?);
+        close.push(to_f64(&p.close)?);
+        volume.push(to_f64(&p.volume)?);
+        amount.push(to_f64(&p.amount)?);
+    }
+    let ts = Int64Chunked::from_vec("ts", ts)
+        .into_datetime(TimeUnit::Milliseconds, None)
+        .into_series();
+    df! {
+        "code" => code,
+        "ts" => ts,
+        "open" => open,
+        "high" => high,
+        "low" => low,
+        "close" => close,
+        "volume" => volume,
+        "amount" => amount,
+    }
+    .map_err(|e| {
+        Error::custom(
+            ErrorKind::InternalServerError,
+            format!("failed to build price frame: {}", e),
+        )
+    })
+}
+
+fn to_f64(d: &bigdecimal::BigDecimal) -> Result<f64> {
+    d.to_f64().ok_or_else(|| {
+        Error::custom(
+            ErrorKind::InternalServerError,
+            format!("cannot convert {} to f64", d),
+        )
+    })
+}
+
+fn ewm(alpha: f64) -> EWMOptions {
+    EWMOptions {
+        alpha,
+        adjust: false,
+        bias: false,
+        min_periods: 1,
+        ignore_nulls: true,
+    }
+}
+
+/// 按 `code` 分组、按 `ts` 排序后求 `dif`/`dea`/`macd` 列，是 [`super::ema::approximate_macd`]
+/// 的向量化版本
+///
+/// `ewm_mean` 需要足够的历史数据预热才能收敛到与单股票路径一致的值：调用方须像
+/// [`super::ema_approximate_start`] 那样，在传入的 `lf` 里包含请求窗口之前的额外历史，
+/// 并在计算完成后把结果裁剪回原窗口（参见 [`batch_frame`]）。
+pub fn with_macd(lf: LazyFrame, cfg: &FrameCfg) -> LazyFrame {
+    let fast_alpha = 2.0 / (cfg.fast_ema_period as f64 + 1.0);
+    let slow_alpha = 2.0 / (cfg.slow_ema_period as f64 + 1.0);
+    let dea_alpha = 2.0 / (cfg.dea_period as f64 + 1.0);
+
+    lf.sort(["code", "ts"], Default::default())
+        .with_column(
+            col("close")
+                .ewm_mean(ewm(fast_alpha))
+                .over(["code"])
+                .alias("fast_ema"),
+        )
+        .with_column(
+            col("close")
+                .ewm_mean(ewm(slow_alpha))
+                .over(["code"])
+                .alias("slow_ema"),
+        )
+        .with_column((col("fast_ema") - col("slow_ema")).alias("dif"))
+        .with_column(
+            col("dif")
+                .ewm_mean(ewm(dea_alpha))
+                .over(["code"])
+                .alias("dea"),
+        )
+        .with_column((lit(2.0) * (col("dif") - col("dea"))).alias("macd"))
+}
+
+/// 按 `code` 分组求真实波幅（TR）及其滚动均值，输出 `atr`/`atrp` 列
+pub fn with_atrp(lf: LazyFrame, cfg: &FrameCfg) -> LazyFrame {
+    lf.sort(["code", "ts"], Default::default())
+        .with_column(
+            col("close")
+                .shift(1)
+                .over(["code"])
+                .alias("prev_close"),
+        )
+        .with_column(
+            max_horizontal([
+                col("high") - col("low"),
+                (col("high") - col("prev_close")).abs(),
+                (col("low") - col("prev_close")).abs(),
+            ])
+            .unwrap()
+            .alias("true_range"),
+        )
+        .with_column(
+            col("true_range")
+                .rolling_mean(RollingOptionsFixedWindow {
+                    window_size: cfg.atr_period as usize,
+                    min_periods: 1,
+                    ..Default::default()
+                })
+                .over(["code"])
+                .alias("atr"),
+        )
+        .with_column((col("atr") / col("close") * lit(100.0)).alias("atrp"))
+}
+
+/// 缓存文件名需把价格窗口（`start_ts`/`end_ts`）编码进去，否则同一
+/// `code`/`tick`/周期配置下，换一个查询窗口会误命中上一次窗口算出的缓存
+fn cache_path(
+    cfg: &FrameCfg,
+    code: &str,
+    tick: &str,
+    start_ts: NaiveDateTime,
+    end_ts: NaiveDateTime,
+) -> PathBuf {
+    cfg.cache_dir.join(format!(
+        "{}_{}_{}_{}_{}_{}_{}_{}.parquet",
+        code,
+        tick,
+        cfg.fast_ema_period,
+        cfg.slow_ema_period,
+        cfg.dea_period,
+        cfg.atr_period,
+        start_ts.timestamp_millis(),
+        end_ts.timestamp_millis(),
+    ))
+}
+
+/// 计算多股票的 MACD/ATRP 指标帧，优先读取 Parquet 缓存，命中失败才重新计算并写回缓存
+///
+/// 缓存以 `(code, tick, period-cfg, start_ts, end_ts)` 为键，`start_ts`/`end_ts`
+/// 取自 `prices` 首尾两根K线的时间戳，确保换一个查询窗口不会误命中旧缓存。
+/// `prices` 为空时没有窗口可言，直接返回空帧，不做缓存。
+pub fn load_or_compute(prices: &[StockTickPrice], code: &str, tick: &str, cfg: &FrameCfg) -> Result<DataFrame> {
+    let (start_ts, end_ts) = match (prices.first(), prices.last()) {
+        (Some(first), Some(last)) => (first.ts, last.ts),
+        _ => return prices_to_frame(prices),
+    };
+    let path = cache_path(cfg, code, tick, start_ts, end_ts);
+    if path.exists() {
+        return LazyFrame::scan_parquet(&path, ScanArgsParquet::default())
+            .and_then(|lf| lf.collect())
+            .map_err(|e| {
+                Error::custom(
+                    ErrorKind::InternalServerError,
+                    format!("failed to read cached frame {}: {}", path.display(), e),
+                )
+            });
+    }
+
+    let raw = prices_to_frame(prices)?;
+    let mut frame = with_atrp(with_macd(raw.lazy(), cfg), cfg)
+        .collect()
+        .map_err(|e| {
+            Error::custom(
+                ErrorKind::InternalServerError,
+                format!("failed to compute indicator frame: {}", e),
+            )
+        })?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            Error::custom(
+                ErrorKind::InternalServerError,
+                format!("failed to create cache dir {}: {}", parent.display(), e),
+            )
+        })?;
+    }
+    let mut file = File::create(&path).map_err(|e| {
+        Error::custom(
+            ErrorKind::InternalServerError,
+            format!("failed to create cache file {}: {}", path.display(), e),
+        )
+    })?;
+    ParquetWriter::new(&mut file).finish(&mut frame).map_err(|e| {
+        Error::custom(
+            ErrorKind::InternalServerError,
+            format!("failed to write cache file {}: {}", path.display(), e),
+        )
+    })?;
+    Ok(frame)
+}
+
+/// 批量拉取多支股票行情并拼接为统一的 MACD/ATRP 指标帧
+///
+/// 每支股票独立走 [`load_or_compute`]（含各自的 Parquet 缓存），再按行纵向拼接成
+/// 一张多股票帧，供批量场景（如横向对比多支股票的 ATRP）一次性消费。
+pub async fn batch_frame(
+    db: &DbPool,
+    jq: &JqdataClient,
+    codes: &[String],
+    tick: &str,
+    start_ts: NaiveDateTime,
+    end_ts: NaiveDateTime,
+    cfg: &FrameCfg,
+) -> Result<DataFrame> {
+    let fetch_start_ts = ema_fetch_start(start_ts, tick, cfg)?;
+    let mut frames = Vec::with_capacity(codes.len());
+    for code in codes {
+        let prices = get_stock_tick_prices(&db, &jq, tick, code, fetch_start_ts, end_ts).await?;
+        frames.push(load_or_compute(&prices, code, tick, cfg)?);
+    }
+    trim_to_window(vstack_all(frames)?, start_ts)
+}
+
+/// 以本地通达信 `.day` 文件作为行情来源的同款批量管线，使之可以完全脱离jqdata网络请求运行
+pub fn batch_frame_from_tdx(
+    tdx: &jqdata_shell::tdx::TdxDaySource,
+    codes: &[String],
+    tick: &str,
+    start_ts: NaiveDateTime,
+    end_ts: NaiveDateTime,
+    cfg: &FrameCfg,
+) -> Result<DataFrame> {
+    let fetch_start_ts = ema_fetch_start(start_ts, tick, cfg)?;
+    let mut frames = Vec::with_capacity(codes.len());
+    for code in codes {
+        let prices = super::tdx_source::get_tdx_tick_prices(tdx, code, fetch_start_ts, end_ts)?;
+        frames.push(load_or_compute(&prices, code, tick, cfg)?);
+    }
+    trim_to_window(vstack_all(frames)?, start_ts)
+}
+
+/// 与 [`super::get_metrics_macd`] 的 `ema_approximate_start` 同理：`ewm_mean` 需要
+/// 额外历史预热才能收敛，取数窗口须往前回溯，计算完成后再裁剪回请求的窗口
+fn ema_fetch_start(start_ts: NaiveDateTime, tick: &str, cfg: &FrameCfg) -> Result<NaiveDateTime> {
+    let fetch_start_dt = super::ema_approximate_start(start_ts.date(), tick, cfg.slow_ema_period)?;
+    Ok(fetch_start_dt.and_hms(0, 0, 0))
+}
+
+/// 把为预热EMA而多取的历史行裁剪掉，只保留请求窗口 `ts >= start_ts` 的部分
+fn trim_to_window(df: DataFrame, start_ts: NaiveDateTime) -> Result<DataFrame> {
+    df.lazy()
+        .filter(col("ts").gt_eq(lit(start_ts.timestamp_millis()).cast(DataType::Datetime(
+            TimeUnit::Milliseconds,
+            None,
+        ))))
+        .collect()
+        .map_err(|e| {
+            Error::custom(
+                ErrorKind::InternalServerError,
+                format!("failed to trim frame to requested window: {}", e),
+            )
+        })
+}
+
+fn vstack_all(frames: Vec<DataFrame>) -> Result<DataFrame> {
+    let mut iter = frames.into_iter();
+    let first = match iter.next() {
+        Some(f) => f,
+        None => {
+            return Err(Error::custom(
+                ErrorKind::BadRequest,
+                "batch_frame requires at least one code".to_owned(),
+            ))
+        }
+    };
+    iter.try_fold(first, |acc, f| {
+        acc.vstack(&f).map_err(|e| {
+            Error::custom(
+                ErrorKind::InternalServerError,
+                format!("failed to stack frames: {}", e),
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn new_price(ts: &str, close: f64) -> StockTickPrice {
+        let ts = NaiveDateTime::parse_from_str(&format!("{} 00:00", ts), "%Y-%m-%d %H:%M").unwrap();
+        StockTickPrice {
+            tick: "1d".to_owned(),
+            code: "000001".to_owned(),
+            ts,
+            open: BigDecimal::from(close),
+            high: BigDecimal::from(close),
+            low: BigDecimal::from(close),
+            close: BigDecimal::from(close),
+            volume: BigDecimal::from(100),
+            amount: BigDecimal::from(close * 100.0),
+        }
+    }
+
+    #[test]
+    fn test_prices_to_frame_row_count_and_columns() -> Result<()> {
+        let prices = vec![new_price("2020-01-01", 10.0), new_price("2020-01-02", 11.0)];
+        let df = prices_to_frame(&prices)?;
+        assert_eq!(2, df.height());
+        assert_eq!(8, df.width());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_path_differs_across_ts_range() {
+        let cfg = FrameCfg::default();
+        let a = cache_path(
+            &cfg,
+            "000001",
+            "1d",
+            new_price("2020-01-01", 10.0).ts,
+            new_price("2020-01-31", 10.0).ts,
+        );
+        let b = cache_path(
+            &cfg,
+            "000001",
+            "1d",
+            new_price("2020-01-01", 10.0).ts,
+            new_price("2020-02-28", 10.0).ts,
+        );
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_load_or_compute_skips_cache_for_empty_prices() -> Result<()> {
+        let cfg = FrameCfg::default();
+        let df = load_or_compute(&[], "000001", "1d", &cfg)?;
+        assert_eq!(0, df.height());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trim_to_window_drops_warm_up_rows() -> Result<()> {
+        // 预热用的历史行（2020-01-01）应在裁剪后被去掉，只保留请求窗口内的行
+        let prices = vec![new_price("2020-01-01", 10.0), new_price("2020-01-10", 11.0)];
+        let df = prices_to_frame(&prices)?;
+        let trimmed = trim_to_window(df, new_price("2020-01-10", 0.0).ts)?;
+        assert_eq!(1, trimmed.height());
+        Ok(())
+    }
+}
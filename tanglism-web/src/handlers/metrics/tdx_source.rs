@@ -0,0 +1,82 @@
+use crate::models::StockTickPrice;
+use crate::{Error, ErrorKind, Result};
+use chrono::NaiveDateTime;
+use jqdata_shell::tdx::{DayBar, TdxDaySource};
+
+/// 本地通达信日线记录到行情模型的转换：字段一一对应
+impl From<DayBar> for StockTickPrice {
+    fn from(bar: DayBar) -> Self {
+        StockTickPrice {
+            code: bar.code,
+            tick: bar.tick,
+            ts: bar.ts,
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+            amount: bar.amount,
+        }
+    }
+}
+
+/// 从本地 `.day` 文件读取某股票在 `[start_ts, end_ts]` 内的行情，按 `ts` 升序排列，
+/// 作为 [`super::super::stock_prices::get_stock_tick_prices`] 的离线替代，
+/// 使MACD/ATR管线可以完全脱离jqdata网络请求运行
+pub fn get_tdx_tick_prices(
+    tdx: &TdxDaySource,
+    code: &str,
+    start_ts: NaiveDateTime,
+    end_ts: NaiveDateTime,
+) -> Result<Vec<StockTickPrice>> {
+    let bars = tdx.get_day_bars(code).map_err(|e| {
+        Error::custom(
+            ErrorKind::InternalServerError,
+            format!("failed to read local tdx bars for {}: {}", code, e),
+        )
+    })?;
+    Ok(bars
+        .into_iter()
+        .filter(|b| b.ts >= start_ts && b.ts <= end_ts)
+        .map(StockTickPrice::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use jqdata_shell::tdx::TdxDayConfig;
+
+    fn new_bar(ts: &str, close: f64) -> DayBar {
+        let ts = NaiveDateTime::parse_from_str(&format!("{} 00:00", ts), "%Y-%m-%d %H:%M").unwrap();
+        DayBar {
+            code: "000001".to_owned(),
+            tick: "1d".to_owned(),
+            ts,
+            open: BigDecimal::from(close),
+            high: BigDecimal::from(close),
+            low: BigDecimal::from(close),
+            close: BigDecimal::from(close),
+            volume: BigDecimal::from(100),
+            amount: BigDecimal::from(close * 100.0),
+        }
+    }
+
+    #[test]
+    fn test_day_bar_into_stock_tick_price() {
+        let bar = new_bar("2020-01-01", 10.0);
+        let price: StockTickPrice = bar.into();
+        assert_eq!("000001", price.code);
+        assert_eq!("1d", price.tick);
+        assert_eq!(BigDecimal::from(10.0), price.close);
+    }
+
+    #[test]
+    fn test_get_tdx_tick_prices_missing_code_errors() {
+        let tdx = TdxDaySource::new(TdxDayConfig::new());
+        let start = NaiveDateTime::parse_from_str("2020-01-01 00:00", "%Y-%m-%d %H:%M").unwrap();
+        let end = NaiveDateTime::parse_from_str("2020-12-31 00:00", "%Y-%m-%d %H:%M").unwrap();
+        assert!(get_tdx_tick_prices(&tdx, "000001", start, end).is_err());
+    }
+}